@@ -0,0 +1,125 @@
+//! Read-only HTTP query API for stored sensor data. Backs the `Serve` subcommand.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use super::{database_path, ServerConfig};
+
+#[derive(Serialize, sqlx::FromRow)]
+struct Microcontroller {
+    id: i64
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct Sensor {
+    m_id: i64,
+    s_id: i64
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct SensorDataRow {
+    id: i64,
+    timepoint: Option<String>,
+    m_id: i64,
+    s_id: i64,
+    sensor_type: i64,
+    sensor_data: i64,
+    dummy: bool
+}
+
+/// Query parameters accepted by `GET /sensors/{m_id}/{s_id}/data`.
+#[derive(Deserialize)]
+struct SensorDataQuery {
+    from: Option<String>,
+    to: Option<String>,
+    dummy: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>
+}
+
+static DEFAULT_LIMIT: i64 = 100;
+static MAX_LIMIT: i64 = 1000;
+
+async fn list_microcontrollers(
+    State(pool): State<SqlitePool>
+) -> Result<Json<Vec<Microcontroller>>, (StatusCode, String)> {
+    sqlx::query_as("SELECT id FROM Microcontrollers ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn list_sensors(
+    State(pool): State<SqlitePool>,
+    Path(m_id): Path<i64>
+) -> Result<Json<Vec<Sensor>>, (StatusCode, String)> {
+    sqlx::query_as("SELECT m_id, s_id FROM Sensors WHERE m_id = $1 ORDER BY s_id")
+        .bind(m_id)
+        .fetch_all(&pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn sensor_data(
+    State(pool): State<SqlitePool>,
+    Path((m_id, s_id)): Path<(i64, i64)>,
+    Query(query): Query<SensorDataQuery>
+) -> Result<Json<Vec<SensorDataRow>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    sqlx::query_as(
+        "SELECT id, timepoint, m_id, s_id, sensor_type, sensor_data, dummy
+           FROM SensorData
+          WHERE m_id = $1 AND s_id = $2
+            AND ($3 IS NULL OR timepoint >= $3)
+            AND ($4 IS NULL OR timepoint <= $4)
+            AND ($5 IS NULL OR dummy = $5)
+          ORDER BY timepoint ASC
+          LIMIT $6 OFFSET $7"
+    )
+        .bind(m_id)
+        .bind(s_id)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.dummy)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+fn internal_error(e: sqlx::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn router(pool: SqlitePool) -> Router {
+    Router::new()
+        .route("/microcontrollers", get(list_microcontrollers))
+        .route("/microcontrollers/{m_id}/sensors", get(list_sensors))
+        .route("/sensors/{m_id}/{s_id}/data", get(sensor_data))
+        .with_state(pool)
+}
+
+/// Serves the read-only query API on `server_config.address`.
+pub async fn serve(server_config: &ServerConfig) {
+    let options = sqlx::sqlite::SqliteConnectOptions::new()
+        .filename(database_path(&server_config.database))
+        .read_only(true);
+
+    let pool = sqlx::SqlitePool::connect_with(options).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind(&server_config.address).await.unwrap();
+
+    axum::serve(listener, router(pool)).await.unwrap();
+}
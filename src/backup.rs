@@ -0,0 +1,27 @@
+//! Online backups of the sensor database using SQLite's hot-backup API.
+
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::backup::Backup;
+use rusqlite::Connection;
+
+use super::{database_path, ServerConfig};
+
+/// Copies the configured database to `output`. Backs the `Backup` subcommand.
+pub fn backup(server_config: &ServerConfig, output: &Path, force: bool) -> rusqlite::Result<()> {
+    if output.exists() && !force {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+            Some(format!("{} already exists; pass --force to overwrite", output.display()))
+        ));
+    }
+
+    let source = Connection::open(database_path(&server_config.database))?;
+    let mut destination = Connection::open(output)?;
+
+    let backup = Backup::new(&source, &mut destination)?;
+    backup.run_to_completion(100, Duration::from_millis(50), None)?;
+
+    Ok(())
+}
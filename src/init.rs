@@ -2,6 +2,10 @@ use std::{fs, io, path::{Path, PathBuf}};
 use toml::Table;
 use clap::{Parser, Subcommand};
 
+mod migrations;
+mod backup;
+mod api;
+
 
 #[derive(Parser)]
 #[command(name = "womscp-server")]
@@ -19,17 +23,127 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// initializes the server
-    Init
+    Init,
+
+    /// applies any pending schema migrations without re-initializing
+    Migrate {
+        /// directory of `<version>_<description>.sql` files to apply in
+        /// addition to the migrations built into this binary
+        #[arg(long, value_name = "DIR")]
+        migrations_dir: Option<PathBuf>
+    },
+
+    /// prints the path of the config file the server would load
+    ConfigLocation,
+
+    /// takes a consistent online snapshot of the sensor database
+    Backup {
+        /// file to write the backup to
+        output: PathBuf,
+
+        /// overwrite `output` if it already exists
+        #[arg(long)]
+        force: bool
+    },
+
+    /// serves the read-only HTTP query API on `ServerConfig.address`
+    Serve
 }
 
 
 static DEFAULT_CONFIG :&'static str = "config.toml";
 
+static DEFAULT_CONFIG_TOML :&'static str = "\
+# Address the server binds to.
+address = \"127.0.0.1:3000\"
+
+# sqlx connection string for the sensor database.
+database = \"sqlite:w_orchid.db\"
+
+# Number of microcontrollers to seed on init.
+microcontroller_count = 1
+
+# Number of sensors per microcontroller to seed on init. Either a single
+# integer applied to every microcontroller, or an array giving the count
+# for each microcontroller id, e.g. sensors_per_microcontroller = [2, 4, 1]
+sensors_per_microcontroller = 2
+";
+
+fn xdg_config_home() -> Option<PathBuf> {
+    if let Ok(xdg_home) = std::env::var("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(xdg_home).join("womscp-server"));
+    }
+
+    std::env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".config").join("womscp-server"))
+}
+
+fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(DEFAULT_CONFIG)];
+
+    if let Some(xdg_home) = xdg_config_home() {
+        paths.push(xdg_home.join("config.toml"));
+    }
+
+    let system_dirs = std::env::var("XDG_CONFIG_DIRS").unwrap_or_else(|_| "/etc/xdg".to_string());
+    for dir in system_dirs.split(':').filter(|dir| !dir.is_empty()) {
+        paths.push(PathBuf::from(dir).join("womscp-server").join("config.toml"));
+    }
+
+    paths
+}
+
+pub(crate) fn database_path(database: &str) -> &str {
+    database.strip_prefix("sqlite:").unwrap_or(database)
+}
+
+pub fn resolve_config_path(explicit: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
+
+    config_search_paths().into_iter().find(|path| path.exists())
+}
+
+pub fn write_default_config() -> io::Result<PathBuf> {
+    let dir = xdg_config_home().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "could not determine XDG config directory ($XDG_CONFIG_HOME or $HOME must be set)")
+    })?;
+    let path = dir.join("config.toml");
+
+    if path.exists() {
+        return Ok(path);
+    }
+
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, DEFAULT_CONFIG_TOML)?;
+
+    Ok(path)
+}
+
+#[derive(Clone, Debug)]
+pub enum SensorCounts {
+    Uniform(u8),
+    PerMicrocontroller(Vec<u8>)
+}
+
+impl SensorCounts {
+    pub fn for_microcontroller(&self, m_id: u16) -> u8 {
+        match self {
+            SensorCounts::Uniform(count) => *count,
+            SensorCounts::PerMicrocontroller(counts) => {
+                counts.get(m_id as usize).copied().unwrap_or(0)
+            }
+        }
+    }
+}
+
 pub struct ServerConfig {
     pub address :String,
     pub database :String,
     pub microcontroller_count :u16,
-    pub sensors_per_microcontroller :u8
+    pub sensors_per_microcontroller :SensorCounts
 }
 
 
@@ -39,16 +153,22 @@ impl ServerConfig {
             address: "127.0.0.1:3000".to_string(),
             database: "sqlite:w_orchid.db".to_string(),
             microcontroller_count: 1,
-            sensors_per_microcontroller: 2
+            sensors_per_microcontroller: SensorCounts::Uniform(2)
         }
     }
 
-    pub fn new() -> Self {
-        // NOTE: Default values for server config.
-        if !Path::new(DEFAULT_CONFIG).exists() {
-            Self::default()
-        } else {
-            DEFAULT_CONFIG.try_into().unwrap()
+    pub fn new() -> io::Result<Self> {
+        Self::with_override(None)
+    }
+
+    pub fn with_override(explicit: Option<&Path>) -> io::Result<Self> {
+        match resolve_config_path(explicit) {
+            Some(path) => path.try_into(),
+            None => {
+                let mut server_config = Self::default();
+                server_config.apply_env_overrides()?;
+                Ok(server_config)
+            }
         }
     }
 }
@@ -95,80 +215,137 @@ impl TryFrom<PathBuf> for ServerConfig {
             server_config.microcontroller_count
         };
 
-        server_config.sensors_per_microcontroller = if let Some(_count) = 
-            config["sensors_per_microcontroller"].as_integer() {
-                _count as u8
+        server_config.sensors_per_microcontroller = match &config["sensors_per_microcontroller"] {
+            toml::Value::Integer(count) => SensorCounts::Uniform(*count as u8),
+            toml::Value::Array(counts) => SensorCounts::PerMicrocontroller(
+                counts.iter()
+                    .map(|count| count.as_integer().unwrap_or(0) as u8)
+                    .collect()
+            ),
+            _ => server_config.sensors_per_microcontroller
+        };
+
+        server_config.apply_env_overrides()?;
+
+        Ok(server_config)
+    }
+}
+
+impl ServerConfig {
+    fn apply_env_overrides(&mut self) -> io::Result<()> {
+        dotenvy::dotenv().ok();
+
+        if let Ok(address) = std::env::var("WOMSCP_ADDRESS") {
+            self.address = address;
+        }
+
+        if let Ok(database) = std::env::var("WOMSCP_DATABASE") {
+            self.database = database;
+        }
+
+        if let Ok(count) = std::env::var("WOMSCP_MICROCONTROLLER_COUNT") {
+            self.microcontroller_count = count.parse().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("WOMSCP_MICROCONTROLLER_COUNT={:?} is not a valid u16: {}", count, e)
+                )
+            })?;
+        }
+
+        if let Ok(raw) = std::env::var("WOMSCP_SENSORS_PER_MICROCONTROLLER") {
+            self.sensors_per_microcontroller = if raw.contains(',') {
+                let counts: Result<Vec<u8>, _> = raw.split(',').map(|count| count.trim().parse()).collect();
+                SensorCounts::PerMicrocontroller(counts.map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("WOMSCP_SENSORS_PER_MICROCONTROLLER={:?} is not a valid comma-separated list of u8: {}", raw, e)
+                    )
+                })?)
             } else {
-                server_config.sensors_per_microcontroller
+                SensorCounts::Uniform(raw.parse().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("WOMSCP_SENSORS_PER_MICROCONTROLLER={:?} is not a valid u8: {}", raw, e)
+                    )
+                })?)
             };
+        }
 
-        Ok(server_config)
+        Ok(())
     }
 }
 
 
 pub async fn server_init(server_config :&ServerConfig) {
     let options = sqlx::sqlite::SqliteConnectOptions::new()
-        .filename(&server_config.database)
+        .filename(database_path(&server_config.database))
         .create_if_missing(true);
 
     let conn = sqlx::SqlitePool::connect_with(options).await.unwrap();
 
-    if let Err(e) = sqlx::query("
-CREATE TABLE Microcontrollers(
-       id INTEGER PRIMARY KEY AUTOINCREMENT);
-
-
-CREATE TABLE Sensors(
-	m_id INT NOT NULL,
-    s_id INT NOT NULL,
-    PRIMARY KEY (m_id, s_id),
-	FOREIGN KEY (m_id) REFERENCES Microcontrollers(id) ON DELETE CASCADE);
-
-
-CREATE TABLE SensorData(
-       id INTEGER PRIMARY KEY AUTOINCREMENT,
-       timepoint TEXT NOT NULL,
-       m_id INT NOT NULL,
-       s_id INT NOT NULL,
-       sensor_type INT NOT NULL,
-       sensor_data INT NOT NULL,
-       dummy BOOLEAN NOT NULL,       
-       FOREIGN KEY (m_id, s_id) REFERENCES Sensors(m_id, s_id) ON DELETE CASCADE,       
-       FOREIGN KEY (m_id) REFERENCES Microcontrollers(id) ON DELETE CASCADE);
-        "
-    )
-    .execute(&conn)
-    .await 
-    {
-            panic!("Failed to create database tables.\n{:#?}", e);
+    if let Err(e) = migrations::run_migrations(&conn, None).await {
+        panic!("Failed to apply the schema migrations.\n{:#?}", e);
     }
 
+    let mut tx = conn.begin().await.unwrap();
+
     for m_id in 0..server_config.microcontroller_count {
         if let Err(e) = sqlx::query(
             "INSERT INTO Microcontrollers VALUES($1)"
         )
             .bind(m_id)
-            .execute(&conn)
+            .execute(&mut *tx)
             .await
         {
+            tx.rollback().await.ok();
             panic!("Failed to insert into Microntrollers.\n{:#?}", e);
         }
 
-        for s_id in 0..server_config.sensors_per_microcontroller {
+        for s_id in 0..server_config.sensors_per_microcontroller.for_microcontroller(m_id) {
             if let Err(e) = sqlx::query(
                 "INSERT INTO Sensors VALUES($1, $2)"
             )
                 .bind(m_id)
                 .bind(s_id)
-                .execute(&conn)
+                .execute(&mut *tx)
                 .await
             {
-                panic!("Failed to insert into Sensors, s_id={}, m_id={}.\n{:#?}", 
+                tx.rollback().await.ok();
+                panic!("Failed to insert into Sensors, s_id={}, m_id={}.\n{:#?}",
                     s_id, m_id, e);
             }
         }
     }
 
+    if let Err(e) = tx.commit().await {
+        panic!("Failed to commit the seeded microcontrollers and sensors.\n{:#?}", e);
+    }
+
     conn.close().await;
 }
+
+pub async fn migrate(server_config: &ServerConfig, migrations_dir: Option<&Path>) {
+    let options = sqlx::sqlite::SqliteConnectOptions::new()
+        .filename(database_path(&server_config.database))
+        .create_if_missing(true);
+
+    let conn = sqlx::SqlitePool::connect_with(options).await.unwrap();
+
+    match migrations::run_migrations(&conn, migrations_dir).await {
+        Ok(applied) if applied.is_empty() => println!("Already up to date."),
+        Ok(applied) => println!("Applied migrations: {:?}", applied),
+        Err(e) => panic!("Failed to apply migrations.\n{:#?}", e),
+    }
+
+    conn.close().await;
+}
+
+pub fn server_backup(server_config: &ServerConfig, output: &Path, force: bool) {
+    if let Err(e) = backup::backup(server_config, output, force) {
+        panic!("Failed to back up the database.\n{:#?}", e);
+    }
+}
+
+pub async fn server_serve(server_config: &ServerConfig) {
+    api::serve(server_config).await;
+}
@@ -0,0 +1,180 @@
+//! Versioned schema migrations, modeled on sqlx's own migrator.
+
+use std::{
+    fs,
+    io,
+    path::Path,
+};
+
+use sqlx::SqlitePool;
+
+/// A single migration step embedded in the binary. Migrations discovered from
+/// `.sql` files on disk are handled as `(version, sql)` pairs instead.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// The schema as it ships with the server, in ascending version order.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        description: "initial schema",
+        sql: "
+CREATE TABLE IF NOT EXISTS Microcontrollers(
+       id INTEGER PRIMARY KEY AUTOINCREMENT);
+
+
+CREATE TABLE IF NOT EXISTS Sensors(
+	m_id INT NOT NULL,
+    s_id INT NOT NULL,
+    PRIMARY KEY (m_id, s_id),
+	FOREIGN KEY (m_id) REFERENCES Microcontrollers(id) ON DELETE CASCADE);
+
+
+CREATE TABLE IF NOT EXISTS SensorData(
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       timepoint TEXT NOT NULL,
+       m_id INT NOT NULL,
+       s_id INT NOT NULL,
+       sensor_type INT NOT NULL,
+       sensor_data INT NOT NULL,
+       dummy BOOLEAN NOT NULL,
+       FOREIGN KEY (m_id, s_id) REFERENCES Sensors(m_id, s_id) ON DELETE CASCADE,
+       FOREIGN KEY (m_id) REFERENCES Microcontrollers(id) ON DELETE CASCADE);
+        ",
+    },
+    Migration {
+        version: 1,
+        description: "auto-populate SensorData.timepoint via a managed-timestamp trigger",
+        // SQLite has no ALTER COLUMN, so relaxing NOT NULL means rebuilding the table.
+        sql: "
+ALTER TABLE SensorData RENAME TO SensorData_old;
+
+CREATE TABLE SensorData(
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       timepoint TEXT,
+       m_id INT NOT NULL,
+       s_id INT NOT NULL,
+       sensor_type INT NOT NULL,
+       sensor_data INT NOT NULL,
+       dummy BOOLEAN NOT NULL,
+       FOREIGN KEY (m_id, s_id) REFERENCES Sensors(m_id, s_id) ON DELETE CASCADE,
+       FOREIGN KEY (m_id) REFERENCES Microcontrollers(id) ON DELETE CASCADE);
+
+INSERT INTO SensorData SELECT * FROM SensorData_old;
+
+DROP TABLE SensorData_old;
+
+CREATE TRIGGER sensor_data_default_timepoint
+AFTER INSERT ON SensorData
+FOR EACH ROW
+WHEN NEW.timepoint IS NULL OR NEW.timepoint = ''
+BEGIN
+    UPDATE SensorData
+    SET timepoint = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+    WHERE id = NEW.id;
+END;
+        ",
+    },
+];
+
+/// Ensures the `_migrations` bookkeeping table exists and returns the highest
+/// version already applied, or `-1` if no migration has ever run.
+async fn ensure_bookkeeping_table(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS _migrations(
+               version INTEGER PRIMARY KEY,
+               applied_at TEXT NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+
+    let current: (i64,) = sqlx::query_as("SELECT COALESCE(MAX(version), -1) FROM _migrations")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(current.0)
+}
+
+/// Reads every `*.sql` file in `dir` named `<version>_<description>.sql` and
+/// returns the ones with a version greater than `applied`.
+fn discover_file_migrations(dir: &Path, applied: i64) -> Result<Vec<(i64, String)>, io::Error> {
+    let mut found = Vec::new();
+
+    if !dir.is_dir() {
+        return Ok(found);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some("sql") {
+            continue;
+        }
+
+        let file_name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+
+        let version: i64 = match file_name.split('_').next().and_then(|v| v.parse().ok()) {
+            Some(version) => version,
+            None => continue,
+        };
+
+        if version <= applied {
+            continue;
+        }
+
+        found.push((version, fs::read_to_string(&path)?));
+    }
+
+    Ok(found)
+}
+
+/// Applies every embedded and on-disk migration newer than the highest
+/// version already recorded, each inside its own transaction.
+pub async fn run_migrations(
+    pool: &SqlitePool,
+    migrations_dir: Option<&Path>,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let applied = ensure_bookkeeping_table(pool).await?;
+
+    let mut pending: Vec<(i64, String)> = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > applied)
+        .map(|migration| (migration.version, migration.sql.to_string()))
+        .collect();
+
+    if let Some(dir) = migrations_dir {
+        let file_migrations = discover_file_migrations(dir, applied)
+            .map_err(sqlx::Error::Io)?;
+        pending.extend(file_migrations);
+    }
+
+    pending.sort_by_key(|(version, _)| *version);
+
+    let mut applied_versions = Vec::new();
+
+    for (version, sql) in pending {
+        let mut tx = pool.begin().await?;
+
+        if let Err(e) = sqlx::query(&sql).execute(&mut *tx).await {
+            tx.rollback().await.ok();
+            return Err(e);
+        }
+
+        sqlx::query("INSERT INTO _migrations (version, applied_at) VALUES ($1, datetime('now'))")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        applied_versions.push(version);
+    }
+
+    Ok(applied_versions)
+}